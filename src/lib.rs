@@ -1,29 +1,62 @@
 extern crate nom;
 
 use nom::{
+    branch::alt,
     bytes::complete::{tag, take, take_until},
     combinator::{map, map_opt, map_parser, map_res, opt, rest},
+    error::{context, VerboseError, VerboseErrorKind},
     sequence::tuple,
-    IResult,
+    IResult, Offset,
 };
 use std::error;
 use std::fmt;
 
+type VResult<'a, T> = IResult<&'a str, T, VerboseError<&'a str>>;
+
 #[derive(Debug)]
-pub struct TLEError;
+pub enum TLEError {
+    InvalidFormat,
+    /// A line's trailing checksum digit doesn't match the modulo-10 sum of
+    /// its contents.
+    ChecksumMismatch { line: u8, expected: u8, found: u8 },
+    /// One record out of a multi-TLE input failed to parse.
+    RecordParse { line: usize, source: Box<TLEError> },
+    /// A single field failed to parse; `column` is the 1-indexed byte
+    /// offset into the input where the offending field begins.
+    FieldParse { field: &'static str, column: usize },
+}
 
 pub type Result<T> = std::result::Result<T, TLEError>;
 
 impl fmt::Display for TLEError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Invalid TLE Format")
+        match self {
+            TLEError::InvalidFormat => write!(f, "Invalid TLE Format"),
+            TLEError::ChecksumMismatch {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "checksum mismatch on line {}: expected {}, found {}",
+                line, expected, found
+            ),
+            TLEError::RecordParse { line, source } => {
+                write!(f, "record starting at line {} is invalid: {}", line, source)
+            }
+            TLEError::FieldParse { field, column } => {
+                write!(f, "invalid `{}` field at column {}", field, column)
+            }
+        }
     }
 }
 
 impl error::Error for TLEError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        // Generic error, underlying cause isn't tracked.
-        None
+        match self {
+            TLEError::RecordParse { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
     }
 }
 
@@ -33,7 +66,8 @@ pub struct TLE {
     pub satellite_number: u32,
     pub classification: char,
     pub international_designator: String,
-    // TODO: DateTime<Utc>
+    // Raw epoch as it appears on the TLE, e.g. "20044.88470557". Use
+    // `epoch_datetime()` (requires the `chrono` feature) to get a real timestamp.
     pub epoch: String,
     pub first_derivative_mean_motion: f64,
     pub second_derivative_mean_motion: f64,
@@ -49,24 +83,100 @@ pub struct TLE {
     pub revolution_number: u32,
 }
 
-// 36258-4 => 0.36258e-4
-fn ugly_float_parser(input: &str) -> IResult<&str, f64> {
+// 36258-4 => 0.36258e-4, 36258+1 => 0.36258e+1
+fn ugly_float_parser(input: &str) -> VResult<'_, f64> {
     map_res(
-        tuple((opt(tag("-")), take_until("-"), tag("-"), rest)),
-        |(sign, a, _, b): (Option<&str>, &str, &str, &str)| {
-            format!("{}0.{}e-{}", sign.unwrap_or(""), a, b).parse::<f64>()
+        tuple((opt(tag("-")), take(5usize), alt((tag("-"), tag("+"))), rest)),
+        |(sign, a, exp_sign, b): (Option<&str>, &str, &str, &str)| {
+            format!("{}0.{}e{}{}", sign.unwrap_or(""), a, exp_sign, b).parse::<f64>()
         },
     )(input)
 }
 
-fn satellite_number_parser(input: &str) -> IResult<&str, u32> {
+fn satellite_number_parser(input: &str) -> VResult<'_, u32> {
     map_res(take(5usize), |i: &str| i.parse::<u32>())(input)
 }
 
-fn one_space_parser(input: &str) -> IResult<&str, &str> {
+fn one_space_parser(input: &str) -> VResult<'_, &str> {
     tag(" ")(input)
 }
 
+// Turns a nom parse failure into a `TLEError::FieldParse` naming the
+// innermost `context(...)` that was active when the failure occurred, and
+// the column (1-indexed byte offset into `raw_tle`) at which that field begins.
+fn field_parse_error(raw_tle: &str, err: nom::Err<VerboseError<&str>>) -> TLEError {
+    let verbose = match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        nom::Err::Incomplete(_) => return TLEError::InvalidFormat,
+    };
+
+    verbose
+        .errors
+        .iter()
+        .find_map(|(input, kind)| match kind {
+            VerboseErrorKind::Context(field) => Some(TLEError::FieldParse {
+                field,
+                column: raw_tle.offset(input) + 1,
+            }),
+            _ => None,
+        })
+        .unwrap_or(TLEError::InvalidFormat)
+}
+
+// Modulo-10 checksum: sum every digit character, add 1 for every minus
+// sign, and treat everything else (letters, spaces, periods, plus signs) as
+// 0. `line` should not include its own trailing checksum digit.
+fn checksum_sum(line: &str) -> u8 {
+    (line
+        .chars()
+        .map(|c| match c {
+            '0'..='9' => c.to_digit(10).unwrap(),
+            '-' => 1,
+            _ => 0,
+        })
+        .sum::<u32>()
+        % 10) as u8
+}
+
+/// Checks a single TLE line's trailing checksum digit against the
+/// modulo-10 sum of the rest of the line.
+pub fn verify_checksum(line: &str) -> bool {
+    let line = line.trim_end();
+    match line.len().checked_sub(1) {
+        Some(split) => {
+            let (body, check) = line.split_at(split);
+            check
+                .parse::<u8>()
+                .is_ok_and(|found| checksum_sum(body) == found)
+        }
+        None => false,
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TLE {
+    /// Decodes the raw `epoch` string into a UTC timestamp.
+    ///
+    /// TLE epochs encode a two-digit year (57-99 -> 1957-1999, 00-56 ->
+    /// 2000-2056) followed by a three-digit day-of-year and a fractional
+    /// day, e.g. `"20044.88470557"` is day 44 of 2020, 0.88470557 days in.
+    pub fn epoch_datetime(&self) -> chrono::DateTime<chrono::Utc> {
+        use chrono::{Duration, TimeZone, Utc};
+
+        let year_digits: i32 = self.epoch[0..2].parse().expect("epoch year");
+        let year = if year_digits < 57 {
+            2000 + year_digits
+        } else {
+            1900 + year_digits
+        };
+        let day_of_year: f64 = self.epoch[2..].parse().expect("epoch day-of-year");
+
+        Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap()
+            + Duration::days((day_of_year - 1.0) as i64)
+            + Duration::nanoseconds((day_of_year.fract() * 86_400_000_000_000.0).round() as i64)
+    }
+}
+
 pub fn parse(raw_tle: &str) -> Result<TLE> {
     let (
         _,
@@ -123,69 +233,134 @@ pub fn parse(raw_tle: &str) -> Result<TLE> {
         map_parser(
             take_until("\n"),
             tuple((
-                tag("1"),
+                context("line1_marker", tag("1")),
                 one_space_parser,
-                satellite_number_parser,
-                map_opt(take(1usize), |i: &str| i.chars().nth(0usize)),
+                context("satellite_number", satellite_number_parser),
+                context(
+                    "classification",
+                    map_opt(take(1usize), |i: &str| i.chars().next()),
+                ),
                 one_space_parser,
-                map(take(8usize), |i: &str| i.trim()),
+                context(
+                    "international_designator",
+                    map(take(8usize), |i: &str| i.trim()),
+                ),
                 one_space_parser,
-                map(take(14usize), |i: &str| i.trim()),
+                context("epoch", map(take(14usize), |i: &str| i.trim())),
                 one_space_parser,
-                map_res(map(take(10usize), |i: &str| i.trim()), |i: &str| {
-                    i.parse::<f64>()
-                }),
+                context(
+                    "first_derivative_mean_motion",
+                    map_res(map(take(10usize), |i: &str| i.trim()), |i: &str| {
+                        i.parse::<f64>()
+                    }),
+                ),
                 one_space_parser,
-                map_parser(map(take(8usize), |i: &str| i.trim()), ugly_float_parser),
+                context(
+                    "second_derivative_mean_motion",
+                    map_parser(map(take(8usize), |i: &str| i.trim()), ugly_float_parser),
+                ),
                 one_space_parser,
-                map_parser(map(take(8usize), |i: &str| i.trim()), ugly_float_parser),
+                context(
+                    "drag_term",
+                    map_parser(map(take(8usize), |i: &str| i.trim()), ugly_float_parser),
+                ),
                 one_space_parser,
-                map_res(take(1usize), |i: &str| i.parse::<u32>()),
+                context(
+                    "ephemeris_type",
+                    map_res(take(1usize), |i: &str| i.parse::<u32>()),
+                ),
                 one_space_parser,
-                map_res(map(take(4usize), |i: &str| i.trim()), |i: &str| {
-                    i.parse::<u32>()
-                }),
-                map_res(take(1usize), |i: &str| i.parse::<u32>()),
+                context(
+                    "element_number",
+                    map_res(map(take(4usize), |i: &str| i.trim()), |i: &str| {
+                        i.parse::<u32>()
+                    }),
+                ),
+                context(
+                    "checksum",
+                    map_res(take(1usize), |i: &str| i.parse::<u32>()),
+                ),
             )),
         ),
         tag("\n"),
         // second line parser
         tuple((
-            tag("2"),
+            context("line2_marker", tag("2")),
             one_space_parser,
-            satellite_number_parser,
+            context("satellite_number", satellite_number_parser),
             one_space_parser,
-            map_res(map(take(8usize), |i: &str| i.trim()), |i: &str| {
-                i.parse::<f64>()
-            }),
+            context(
+                "inclination",
+                map_res(map(take(8usize), |i: &str| i.trim()), |i: &str| {
+                    i.parse::<f64>()
+                }),
+            ),
             one_space_parser,
-            map_res(map(take(8usize), |i: &str| i.trim()), |i: &str| {
-                i.parse::<f64>()
-            }),
+            context(
+                "right_ascension",
+                map_res(map(take(8usize), |i: &str| i.trim()), |i: &str| {
+                    i.parse::<f64>()
+                }),
+            ),
             one_space_parser,
-            map_res(take(7usize), |i: &str| format!("0.{}", i).parse::<f64>()),
+            context(
+                "eccentricity",
+                map_res(take(7usize), |i: &str| format!("0.{}", i).parse::<f64>()),
+            ),
             one_space_parser,
-            map_res(map(take(8usize), |i: &str| i.trim()), |i: &str| {
-                i.parse::<f64>()
-            }),
+            context(
+                "argument_of_perigee",
+                map_res(map(take(8usize), |i: &str| i.trim()), |i: &str| {
+                    i.parse::<f64>()
+                }),
+            ),
             one_space_parser,
-            map_res(map(take(8usize), |i: &str| i.trim()), |i: &str| {
-                i.parse::<f64>()
-            }),
+            context(
+                "mean_anomaly",
+                map_res(map(take(8usize), |i: &str| i.trim()), |i: &str| {
+                    i.parse::<f64>()
+                }),
+            ),
             one_space_parser,
-            map_res(map(take(11usize), |i: &str| i.trim()), |i: &str| {
-                i.parse::<f64>()
-            }),
-            map_res(map(take(5usize), |i: &str| i.trim()), |i: &str| {
-                i.parse::<u32>()
-            }),
-            map_res(take(1usize), |i: &str| i.parse::<u32>()),
+            context(
+                "mean_motion",
+                map_res(map(take(11usize), |i: &str| i.trim()), |i: &str| {
+                    i.parse::<f64>()
+                }),
+            ),
+            context(
+                "revolution_number",
+                map_res(map(take(5usize), |i: &str| i.trim()), |i: &str| {
+                    i.parse::<u32>()
+                }),
+            ),
+            context(
+                "checksum",
+                map_res(take(1usize), |i: &str| i.parse::<u32>()),
+            ),
         )),
     ))(raw_tle)
-    .map_err(|e| {
-        println!("🤔  Error - {}", e);
-        TLEError
-    })?;
+    .map_err(|e| field_parse_error(raw_tle, e))?;
+
+    let line1 = raw_tle.lines().nth(1).unwrap_or("");
+    let line2 = raw_tle.lines().nth(2).unwrap_or("");
+    for (n, line) in [(1u8, line1), (2u8, line2)].iter() {
+        if !verify_checksum(line) {
+            let body = line.trim_end();
+            let body = &body[..body.len().saturating_sub(1)];
+            let found = line
+                .trim_end()
+                .chars()
+                .last()
+                .and_then(|c| c.to_digit(10))
+                .unwrap_or(0) as u8;
+            return Err(TLEError::ChecksumMismatch {
+                line: *n,
+                expected: checksum_sum(body),
+                found,
+            });
+        }
+    }
 
     Ok(TLE {
         name: String::from(name),
@@ -208,6 +383,120 @@ pub fn parse(raw_tle: &str) -> Result<TLE> {
     })
 }
 
+// 0.36258e-4 => " 36258-4" (inverse of `ugly_float_parser`): a sign, a
+// 5-digit mantissa, and a single-digit signed exponent, decimal point
+// assumed before the mantissa.
+fn ugly_float_formatter(v: f64) -> String {
+    if v == 0.0 {
+        return " 00000-0".to_string();
+    }
+
+    let sign = if v.is_sign_negative() { '-' } else { ' ' };
+    let abs = v.abs();
+    let mut exp = abs.log10().floor() as i32 + 1;
+    let mut mantissa = (abs / 10f64.powi(exp) * 100_000.0).round() as i64;
+    if mantissa >= 100_000 {
+        mantissa /= 10;
+        exp += 1;
+    }
+
+    let exp_sign = if exp < 0 { '-' } else { '+' };
+    format!("{}{:05}{}{}", sign, mantissa, exp_sign, exp.abs())
+}
+
+// 0.00000320 => " .00000320": a sign and 8 fractional digits, no leading
+// zero, decimal point assumed.
+fn signed_decimal_formatter(v: f64) -> String {
+    let sign = if v.is_sign_negative() { '-' } else { ' ' };
+    let digits = format!("{:.8}", v.abs());
+    format!("{}{}", sign, &digits[1..])
+}
+
+impl fmt::Display for TLE {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line1_body = format!(
+            "1 {:05}{} {:<8} {} {} {} {} {} {:>4}",
+            self.satellite_number,
+            self.classification,
+            self.international_designator,
+            self.epoch,
+            signed_decimal_formatter(self.first_derivative_mean_motion),
+            ugly_float_formatter(self.second_derivative_mean_motion),
+            ugly_float_formatter(self.drag_term),
+            self.ephemeris_type,
+            self.element_number,
+        );
+
+        let line2_body = format!(
+            "2 {:05} {:>8.4} {:>8.4} {:07} {:>8.4} {:>8.4} {:>11.8}{:>5}",
+            self.satellite_number,
+            self.inclination,
+            self.right_ascension,
+            (self.eccentricity * 1e7).round() as u32,
+            self.argument_of_perigee,
+            self.mean_anomaly,
+            self.mean_motion,
+            self.revolution_number,
+        );
+
+        writeln!(f, "{}", self.name)?;
+        writeln!(f, "{}{}", line1_body, checksum_sum(&line1_body))?;
+        write!(f, "{}{}", line2_body, checksum_sum(&line2_body))
+    }
+}
+
+/// Parses a catalog of concatenated TLEs (the format used by NORAD and
+/// Celestrak feeds): repeated name/line-1/line-2 triples, one record per
+/// three lines. Also tolerates the common 2-line form with no name line,
+/// falling back to the satellite number as the name. Blank lines between
+/// records are skipped.
+///
+/// Yields one `Result` per record; a record that fails to parse is
+/// reported as `TLEError::RecordParse` carrying the 1-indexed line on
+/// which the record started.
+pub fn parse_many(raw: &str) -> impl Iterator<Item = Result<TLE>> + '_ {
+    let mut lines = raw.lines().enumerate().peekable();
+
+    std::iter::from_fn(move || {
+        let (start_idx, first) = loop {
+            match lines.next() {
+                Some((_, l)) if l.trim().is_empty() => continue,
+                Some((i, l)) => break (i, l),
+                None => return None,
+            }
+        };
+
+        let (name, line1, line2) = if first.starts_with("1 ") {
+            let line2 = lines.next().map_or("", |(_, l)| l);
+            (None, first, line2)
+        } else {
+            let line1 = lines.next().map_or("", |(_, l)| l);
+            let line2 = lines.next().map_or("", |(_, l)| l);
+            (Some(first), line1, line2)
+        };
+
+        let record = format!("{}\n{}\n{}", name.unwrap_or(""), line1, line2);
+
+        let result = parse(&record).map(|mut tle| {
+            if name.is_none() {
+                tle.name = tle.satellite_number.to_string();
+            }
+            tle
+        });
+
+        Some(result.map_err(|e| TLEError::RecordParse {
+            line: start_idx + 1,
+            source: Box::new(e),
+        }))
+    })
+}
+
+/// Like `parse_many`, but collects every record into a `Vec`, stopping at
+/// the first record that fails to parse.
+pub fn parse_all(raw: &str) -> Result<Vec<TLE>> {
+    parse_many(raw).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -222,6 +511,18 @@ mod tests {
 
         let (_, f) = ugly_float_parser("-36258-4").unwrap();
         assert_eq!(f, -0.36258e-4);
+
+        let (_, f) = ugly_float_parser("36258+1").unwrap();
+        assert_eq!(f, 0.36258e1);
+    }
+
+    #[test]
+    fn round_trip_ugly_float_with_non_negative_exponent() {
+        let formatted = ugly_float_formatter(1.5);
+        assert_eq!(formatted, " 15000+1");
+
+        let (_, parsed) = ugly_float_parser(formatted.trim()).unwrap();
+        assert_eq!(parsed, 1.5);
     }
 
     #[test]
@@ -278,8 +579,147 @@ mod tests {
             revolution_number: 21279,
         };
 
-        let tle = parse(&raw_tle).unwrap();
+        let tle = parse(raw_tle).unwrap();
 
         assert_eq!(tle, expected);
     }
+
+    #[test]
+    fn verify_checksum_detects_corruption() {
+        assert!(verify_checksum(
+            "1 43890U 18111Q   20044.88470557  .00000320  00000-0  36258-4 0  9993"
+        ));
+        assert!(!verify_checksum(
+            "1 43890U 18111Q   20044.88470557  .00000320  00000-0  36258-4 0  9994"
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_bad_checksum() {
+        let raw_tle = "GRUS-1A
+1 43890U 18111Q   20044.88470557  .00000320  00000-0  36258-4 0  9994
+2 43890  97.7009 312.6237 0003899   7.8254 352.3026 14.92889838 61757";
+
+        match parse(raw_tle) {
+            Err(TLEError::ChecksumMismatch {
+                line,
+                expected,
+                found,
+            }) => {
+                assert_eq!(line, 1);
+                assert_eq!(expected, 3);
+                assert_eq!(found, 4);
+            }
+            other => panic!("expected ChecksumMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_reports_the_field_and_column_of_a_malformed_value() {
+        let raw_tle = "ISS (ZARYA)
+1 25544U 98067A   20045.18587073  .00000950  00000-0  25302-4 0  9990
+2 25544  XX.6443 242.0161 0004885 264.6060 207.3845 15.49165514212791";
+
+        match parse(raw_tle) {
+            Err(TLEError::FieldParse { field, column }) => {
+                assert_eq!(field, "inclination");
+                assert_eq!(column, 91);
+            }
+            other => panic!("expected FieldParse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trip_grus_tle() {
+        let raw_tle = "GRUS-1A
+1 43890U 18111Q   20044.88470557  .00000320  00000-0  36258-4 0  9993
+2 43890  97.7009 312.6237 0003899   7.8254 352.3026 14.92889838 61757";
+
+        let tle = parse(raw_tle).unwrap();
+
+        assert_eq!(tle.to_string(), raw_tle);
+        assert_eq!(parse(&tle.to_string()).unwrap(), tle);
+    }
+
+    #[test]
+    fn round_trip_iss_tle() {
+        let raw_tle = "ISS (ZARYA)
+1 25544U 98067A   20045.18587073  .00000950  00000-0  25302-4 0  9990
+2 25544  51.6443 242.0161 0004885 264.6060 207.3845 15.49165514212791";
+
+        let tle = parse(raw_tle).unwrap();
+
+        assert_eq!(tle.to_string(), raw_tle);
+        assert_eq!(parse(&tle.to_string()).unwrap(), tle);
+    }
+
+    #[test]
+    fn round_trip_with_small_element_and_revolution_numbers() {
+        let raw_tle = "GRUS-1A
+1 43890U 18111Q   20044.88470557  .00000320  00000-0  36258-4 0  9993
+2 43890  97.7009 312.6237 0003899   7.8254 352.3026 14.92889838 61757";
+
+        let mut tle = parse(raw_tle).unwrap();
+        tle.element_number = 7;
+        tle.revolution_number = 7;
+
+        let encoded = tle.to_string();
+
+        assert!(encoded.lines().nth(1).unwrap().contains("   7"));
+        assert!(encoded.lines().nth(2).unwrap().contains("    7"));
+        assert_eq!(parse(&encoded).unwrap(), tle);
+    }
+
+    #[test]
+    fn parse_many_reads_a_catalog_of_named_tles() {
+        let raw = "GRUS-1A
+1 43890U 18111Q   20044.88470557  .00000320  00000-0  36258-4 0  9993
+2 43890  97.7009 312.6237 0003899   7.8254 352.3026 14.92889838 61757
+
+ISS (ZARYA)
+1 25544U 98067A   20045.18587073  .00000950  00000-0  25302-4 0  9990
+2 25544  51.6443 242.0161 0004885 264.6060 207.3845 15.49165514212791";
+
+        let tles = parse_all(raw).unwrap();
+
+        assert_eq!(tles.len(), 2);
+        assert_eq!(tles[0].name, "GRUS-1A");
+        assert_eq!(tles[1].name, "ISS (ZARYA)");
+    }
+
+    #[test]
+    fn parse_many_falls_back_to_satellite_number_without_a_name_line() {
+        let raw = "1 43890U 18111Q   20044.88470557  .00000320  00000-0  36258-4 0  9993
+2 43890  97.7009 312.6237 0003899   7.8254 352.3026 14.92889838 61757";
+
+        let tles = parse_all(raw).unwrap();
+
+        assert_eq!(tles.len(), 1);
+        assert_eq!(tles[0].name, "43890");
+    }
+
+    #[test]
+    fn parse_many_reports_the_line_of_a_broken_record() {
+        let raw = "GRUS-1A
+1 43890U 18111Q   20044.88470557  .00000320  00000-0  36258-4 0  9994
+2 43890  97.7009 312.6237 0003899   7.8254 352.3026 14.92889838 61757";
+
+        match parse_all(raw) {
+            Err(TLEError::RecordParse { line, .. }) => assert_eq!(line, 1),
+            other => panic!("expected RecordParse, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn epoch_datetime_from_grus_tle() {
+        let raw_tle = "GRUS-1A
+1 43890U 18111Q   20044.88470557  .00000320  00000-0  36258-4 0  9993
+2 43890  97.7009 312.6237 0003899   7.8254 352.3026 14.92889838 61757";
+
+        let tle = parse(raw_tle).unwrap();
+        let datetime = tle.epoch_datetime();
+
+        assert_eq!(datetime.to_rfc3339(), "2020-02-13T21:13:58.561248+00:00");
+    }
 }